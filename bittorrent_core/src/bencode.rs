@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 use thiserror::Error;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Bencode {
     Int(i64),
     /// We use bytes because not all is utf-8
@@ -25,6 +25,16 @@ pub enum BencodeError {
     InvalidBencodeDict,
 }
 
+/// UTF-8 strings longer than this many bytes are cut (at a char boundary) in
+/// [`Bencode::to_debug_string`] and shown with their full length
+const DEBUG_MAX_STRING_LEN: usize = 100;
+/// Binary strings longer than this many bytes are elided to their length and a
+/// hex preview of this many leading bytes
+const DEBUG_HEX_PREVIEW_LEN: usize = 8;
+/// Maximum nesting of lists/dicts accepted by the decoder, deeper input is
+/// rejected instead of recursing until the stack overflows
+const MAX_DEPTH: usize = 64;
+
 pub trait Encode {
     fn to_bencode(&self) -> Bencode;
 }
@@ -37,19 +47,19 @@ impl Encode for String {
 
 impl Bencode {
     pub fn decode(data: &[u8]) -> Result<Bencode, BencodeError> {
-        let (bencode, _rest) = Bencode::decode_recurisvely(data)?;
+        let (bencode, _rest) = Bencode::decode_recurisvely(data, 0)?;
         Ok(bencode)
     }
 
-    fn decode_recurisvely(data: &[u8]) -> Result<(Bencode, &[u8]), BencodeError> {
-        if data.is_empty() {
+    fn decode_recurisvely(data: &[u8], depth: usize) -> Result<(Bencode, &[u8]), BencodeError> {
+        if data.is_empty() || depth >= MAX_DEPTH {
             return Err(BencodeError::InvalidBencode);
         }
         match data[0] {
             b'i' => Bencode::decode_int(data),
             b'0'..=b'9' => Bencode::decode_string(data),
-            b'l' => Bencode::decode_list(data),
-            b'd' => Bencode::decode_dictionary(data),
+            b'l' => Bencode::decode_list(data, depth),
+            b'd' => Bencode::decode_dictionary(data, depth),
             _ => Err(BencodeError::InvalidBencode),
         }
     }
@@ -95,7 +105,7 @@ impl Bencode {
         Ok((Bencode::Int(num), rest))
     }
 
-    fn decode_list(data: &[u8]) -> Result<(Bencode, &[u8]), BencodeError> {
+    fn decode_list(data: &[u8], depth: usize) -> Result<(Bencode, &[u8]), BencodeError> {
         let mut elements = Vec::new();
         let mut current_data = &data[1..];
 
@@ -107,34 +117,72 @@ impl Bencode {
                 return Ok((Bencode::List(elements), &current_data[1..]));
             }
 
-            let (element, rest) = Bencode::decode_recurisvely(current_data)?;
+            let (element, rest) = Bencode::decode_recurisvely(current_data, depth + 1)?;
             elements.push(element);
             current_data = rest;
         }
     }
 
-    fn decode_dictionary(data: &[u8]) -> Result<(Bencode, &[u8]), BencodeError> {
+    fn decode_dictionary(data: &[u8], depth: usize) -> Result<(Bencode, &[u8]), BencodeError> {
         let mut dict = BTreeMap::new();
         let mut current_data = &data[1..];
 
         loop {
+            if current_data.is_empty() {
+                return Err(BencodeError::InvalidBencodeDict);
+            }
             if current_data[0] == b'e' {
                 return Ok((Bencode::Dict(dict), &current_data[1..]));
             }
 
-            let (key, rest_after_key) = Bencode::decode_recurisvely(current_data)?;
+            let (key, rest_after_key) = Bencode::decode_recurisvely(current_data, depth + 1)?;
             let key_bytes = match key {
                 Bencode::Bytes(b) => b,
                 _ => return Err(BencodeError::InvalidBencodeDict),
             };
 
-            let (value, rest_after_value) = Bencode::decode_recurisvely(rest_after_key)?;
+            let (value, rest_after_value) = Bencode::decode_recurisvely(rest_after_key, depth + 1)?;
 
             dict.insert(key_bytes, value);
             current_data = rest_after_value;
         }
     }
 
+    /// Returns the bytes of the value stored under `key` in the top-level dictionary
+    /// of `data`, exactly as they appear in the input. Decoding normalises dicts
+    /// and integers, so this is what hashes (e.g. the info hash) must be taken over.
+    /// As with decoding, the last occurrence of a duplicated key wins.
+    pub fn raw_dict_value<'a>(
+        data: &'a [u8],
+        key: &[u8],
+    ) -> Result<Option<&'a [u8]>, BencodeError> {
+        if data.first() != Some(&b'd') {
+            return Err(BencodeError::InvalidBencodeDict);
+        }
+
+        let mut found = None;
+        let mut current_data = &data[1..];
+        loop {
+            if current_data.is_empty() {
+                return Err(BencodeError::InvalidBencodeDict);
+            }
+            if current_data[0] == b'e' {
+                return Ok(found);
+            }
+
+            let (current_key, value_start) = Bencode::decode_recurisvely(current_data, 1)?;
+            let (_value, rest) = Bencode::decode_recurisvely(value_start, 1)?;
+            match current_key {
+                Bencode::Bytes(bytes) if bytes == key => {
+                    found = Some(&value_start[..value_start.len() - rest.len()]);
+                }
+                Bencode::Bytes(_) => {}
+                _ => return Err(BencodeError::InvalidBencodeDict),
+            }
+            current_data = rest;
+        }
+    }
+
     pub fn get(&self, key: &[u8]) -> Option<&Bencode> {
         match self {
             Bencode::Dict(dict) => dict.get(key),
@@ -142,11 +190,77 @@ impl Bencode {
         }
     }
 
+    /// Renders the tree in an indented, human readable form for debugging.
+    /// Byte strings are shown as UTF-8 when valid, long text is cut with a length
+    /// suffix, and binary blobs like `pieces` are elided to their length and a
+    /// short hex preview.
+    pub fn to_debug_string(&self) -> String {
+        let mut output = String::new();
+        self.write_debug(&mut output, 0);
+        output
+    }
+
+    fn write_debug(&self, output: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth + 1);
+        match self {
+            Bencode::Int(i) => output.push_str(&i.to_string()),
+            Bencode::Bytes(bytes) => output.push_str(&Bencode::debug_bytes(bytes)),
+            Bencode::List(list) if list.is_empty() => output.push_str("[]"),
+            Bencode::List(list) => {
+                output.push_str("[\n");
+                for item in list {
+                    output.push_str(&indent);
+                    item.write_debug(output, depth + 1);
+                    output.push('\n');
+                }
+                output.push_str(&"  ".repeat(depth));
+                output.push(']');
+            }
+            Bencode::Dict(dict) if dict.is_empty() => output.push_str("{}"),
+            Bencode::Dict(dict) => {
+                output.push_str("{\n");
+                for (key, value) in dict {
+                    output.push_str(&indent);
+                    output.push_str(&String::from_utf8_lossy(key));
+                    output.push_str(": ");
+                    value.write_debug(output, depth + 1);
+                    output.push('\n');
+                }
+                output.push_str(&"  ".repeat(depth));
+                output.push('}');
+            }
+        }
+    }
+
+    fn debug_bytes(bytes: &[u8]) -> String {
+        match std::str::from_utf8(bytes) {
+            Ok(text) if text.len() <= DEBUG_MAX_STRING_LEN => format!("{text:?}"),
+            Ok(text) => {
+                let mut end = DEBUG_MAX_STRING_LEN;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                format!("{:?}.. ({} bytes)", &text[..end], text.len())
+            }
+            _ if bytes.len() <= DEBUG_HEX_PREVIEW_LEN => format!("0x{}", hex::encode(bytes)),
+            _ => format!(
+                "<{} bytes 0x{}..>",
+                bytes.len(),
+                hex::encode(&bytes[..DEBUG_HEX_PREVIEW_LEN])
+            ),
+        }
+    }
+
     pub fn encode(bencode: &impl Encode) -> Vec<u8> {
         let bencode = bencode.to_bencode();
         Bencode::encoder(&bencode)
     }
 
+    /// Encodes an existing tree without copying it through [`Encode`]
+    pub fn encode_value(bencode: &Bencode) -> Vec<u8> {
+        Bencode::encoder(bencode)
+    }
+
     fn encoder(bencode: &Bencode) -> Vec<u8> {
         match bencode {
             Bencode::Int(i) => Bencode::encode_int(*i),
@@ -254,4 +368,200 @@ mod tests {
         dbg!(&expected);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_bencode_encode_integer() {
+        assert_eq!(Bencode::encoder(&Bencode::Int(0)), b"i0e".to_vec());
+        assert_eq!(Bencode::encoder(&Bencode::Int(-42)), b"i-42e".to_vec());
+        assert_eq!(
+            Bencode::encoder(&Bencode::Int(i64::MAX)),
+            b"i9223372036854775807e".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_bencode_encode_list_and_dict() {
+        let input = Bencode::List(vec![
+            Bencode::Bytes(Vec::new()),
+            Bencode::Int(52),
+            Bencode::List(Vec::new()),
+            Bencode::Dict(BTreeMap::new()),
+        ]);
+        assert_eq!(Bencode::encode_value(&input), b"l0:i52eledee".to_vec());
+
+        // Keys are written in sorted order regardless of insertion order
+        let mut dict = BTreeMap::new();
+        dict.insert(b"zz".to_vec(), Bencode::Int(1));
+        dict.insert(b"a".to_vec(), Bencode::Bytes(b"x".to_vec()));
+        assert_eq!(
+            Bencode::encode_value(&Bencode::Dict(dict)),
+            b"d1:a1:x2:zzi1ee".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_bencode_round_trip_krpc_message() {
+        // DHT get_peers response: binary ids and compact blobs containing bytes
+        // that are meaningful to the parser (':', 'e', digits)
+        let node_id = vec![b'e'; 20];
+        let mut nodes = vec![b':'; 26];
+        nodes.extend_from_slice(&[b'1', b'0', 0, 255, b'd', b'l']);
+        let values = vec![
+            Bencode::Bytes(vec![127, 0, 0, 1, 0x1a, 0xe1]),
+            Bencode::Bytes(vec![10, 0, 0, b'e', b':', b'i']),
+        ];
+
+        let mut response = BTreeMap::new();
+        response.insert(b"id".to_vec(), Bencode::Bytes(node_id));
+        response.insert(b"nodes".to_vec(), Bencode::Bytes(nodes));
+        response.insert(b"token".to_vec(), Bencode::Bytes(b"aoeusnth".to_vec()));
+        response.insert(b"values".to_vec(), Bencode::List(values));
+
+        let mut message = BTreeMap::new();
+        message.insert(b"r".to_vec(), Bencode::Dict(response));
+        message.insert(b"t".to_vec(), Bencode::Bytes(b"aa".to_vec()));
+        message.insert(b"y".to_vec(), Bencode::Bytes(b"r".to_vec()));
+        message.insert(
+            b"e".to_vec(),
+            Bencode::List(vec![Bencode::Int(-201), Bencode::Bytes(Vec::new())]),
+        );
+        let message = Bencode::Dict(message);
+
+        let encoded = Bencode::encode_value(&message);
+        let decoded = Bencode::decode(&encoded).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(Bencode::encode_value(&decoded), encoded);
+    }
+
+    #[test]
+    fn test_bencode_raw_dict_value() {
+        let input = b"d4:infod1:bi03e1:ai1ee4:name1:x4:infoi7ee";
+        assert_eq!(
+            Bencode::raw_dict_value(input, b"info"),
+            Ok(Some(&b"i7e"[..]))
+        );
+        assert_eq!(
+            Bencode::raw_dict_value(input, b"name"),
+            Ok(Some(&b"1:x"[..]))
+        );
+        assert_eq!(Bencode::raw_dict_value(input, b"missing"), Ok(None));
+
+        let input = b"d4:infod1:bi03e1:ai1eee";
+        assert_eq!(
+            Bencode::raw_dict_value(input, b"info"),
+            Ok(Some(&b"d1:bi03e1:ai1ee"[..]))
+        );
+
+        assert_eq!(
+            Bencode::raw_dict_value(b"li1ee", b"info"),
+            Err(BencodeError::InvalidBencodeDict)
+        );
+        assert_eq!(
+            Bencode::raw_dict_value(b"d4:info", b"info"),
+            Err(BencodeError::InvalidBencode)
+        );
+    }
+
+    #[test]
+    fn test_bencode_debug_string_nested() {
+        let input =
+            Bencode::decode(b"d4:listli1e0:lee3:raw4:\x00\x01\x02\xff3:str5:helloe").unwrap();
+        let expected =
+            "{\n  list: [\n    1\n    \"\"\n    []\n  ]\n  raw: 0x000102ff\n  str: \"hello\"\n}";
+        assert_eq!(input.to_debug_string(), expected);
+    }
+
+    #[test]
+    fn test_bencode_debug_string_long_text() {
+        // 'é' is two bytes and straddles the cut at DEBUG_MAX_STRING_LEN
+        let text = format!("{}{}", "a".repeat(DEBUG_MAX_STRING_LEN - 1), "é".repeat(10));
+        let output = Bencode::Bytes(text.clone().into_bytes()).to_debug_string();
+        let expected = format!(
+            "\"{}\".. ({} bytes)",
+            "a".repeat(DEBUG_MAX_STRING_LEN - 1),
+            text.len()
+        );
+        assert_eq!(output, expected);
+
+        let binary = vec![0xff; DEBUG_MAX_STRING_LEN * 2];
+        let output = Bencode::Bytes(binary).to_debug_string();
+        assert_eq!(output, "<200 bytes 0xffffffffffffffff..>");
+    }
+
+    #[test]
+    fn test_bencode_debug_string_sample_torrent() {
+        let data = include_bytes!("../../sample_torrents/sample.torrent");
+        let torrent = Bencode::decode(data).unwrap();
+        let output = torrent.to_debug_string();
+        assert!(
+            output
+                .contains("announce: \"http://bittorrent-test-tracker.codecrafters.io/announce\"")
+        );
+        assert!(output.contains("length: 92063"));
+        assert!(output.contains("piece length: 32768"));
+        assert!(output.contains("pieces: <60 bytes 0x"));
+    }
+
+    #[test]
+    fn test_bencode_decode_truncated_dict() {
+        assert_eq!(Bencode::decode(b"d"), Err(BencodeError::InvalidBencodeDict));
+        assert_eq!(
+            Bencode::decode(b"d3:fooi1e"),
+            Err(BencodeError::InvalidBencodeDict)
+        );
+    }
+
+    #[test]
+    fn test_bencode_decode_rejects_deep_nesting() {
+        let mut input = vec![b'l'; 100_000];
+        input.extend(vec![b'e'; 100_000]);
+        assert_eq!(Bencode::decode(&input), Err(BencodeError::InvalidBencode));
+
+        let mut input = vec![b'l'; MAX_DEPTH];
+        input.extend(vec![b'e'; MAX_DEPTH]);
+        assert!(Bencode::decode(&input).is_ok());
+
+        let mut input = vec![b'l'; MAX_DEPTH + 1];
+        input.extend(vec![b'e'; MAX_DEPTH + 1]);
+        assert_eq!(Bencode::decode(&input), Err(BencodeError::InvalidBencode));
+    }
+
+    #[test]
+    fn test_bencode_decode_every_truncation_of_sample_torrent() {
+        let data = include_bytes!("../../sample_torrents/sample.torrent");
+        for end in 0..data.len() {
+            let _ = Bencode::decode(&data[..end]);
+        }
+        assert!(Bencode::decode(data).is_ok());
+    }
+
+    #[test]
+    fn test_bencode_decode_random_input_does_not_panic() {
+        // xorshift, so the inputs are the same on every run
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        // Mostly bencode syntax so the decoder gets past the first byte
+        let alphabet = b"ilde0123456789:-xe";
+        let sample = include_bytes!("../../sample_torrents/sample.torrent");
+        for _ in 0..20_000 {
+            let len = (next() % 64) as usize;
+            let input: Vec<u8> = (0..len)
+                .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                .collect();
+            let _ = Bencode::decode(&input);
+
+            let mut mutated = sample.to_vec();
+            for _ in 0..4 {
+                let index = (next() % mutated.len() as u64) as usize;
+                mutated[index] = next() as u8;
+            }
+            let _ = Bencode::decode(&mutated);
+        }
+    }
 }
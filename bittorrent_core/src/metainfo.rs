@@ -4,7 +4,7 @@ use sha1::{Digest, Sha1};
 use thiserror::Error;
 
 use crate::{
-    bencode::{Bencode, Encode},
+    bencode::{Bencode, BencodeError, Encode},
     types::{InfoHash, PieceHash, PieceHashError},
 };
 
@@ -12,11 +12,23 @@ pub struct Torrent {
     pub announce: String,
     pub info: Info,
     pub info_hash: InfoHash,
+    /// bencoded info dictionary, byte for byte as it appeared in the .torrent file,
+    /// so the info hash and exported metainfo match the original exactly
+    pub raw_info: Vec<u8>,
+    /// every top-level key besides `announce` and `info` (e.g. `comment`,
+    /// `announce-list`, `url-list`), kept as read so exporting doesn't lose them
+    pub extra: BTreeMap<Vec<u8>, Bencode>,
+    /// usable web seed urls, GetRight style (BEP 19 `url-list`), derived from `extra`
+    pub url_list: Vec<String>,
+    /// usable web seed urls, Hoffman style (BEP 17 `httpseeds`), derived from `extra`
+    pub http_seeds: Vec<String>,
 }
 
 pub struct Info {
     /// size of the file in bytes, for single-file torrents
-    pub length: i64,
+    pub length: Option<i64>,
+    /// list of files, for multi-file torrents
+    pub files: Option<Vec<File>>,
     /// Nate to save the file / directory as
     pub name: String,
     /// number of bytes in each piece
@@ -25,16 +37,37 @@ pub struct Info {
     pub pieces: Vec<PieceHash>,
 }
 
+pub struct File {
+    /// size of the file in bytes
+    pub length: i64,
+    /// path components of the file, the last one being the file name
+    pub path: Vec<String>,
+}
+
 #[derive(Debug, PartialEq, Eq, Error)]
 pub enum InfoError {
     #[error("Missing length field in the torrent info")]
     MissingLength,
+    #[error("Invalid length {0}, must not be negative")]
+    InvalidLength(i64),
+    #[error("Invalid files field in the torrent info")]
+    InvalidFiles,
+    #[error("Torrent info has both length and files fields")]
+    AmbiguousLayout,
+    #[error("Total length of the torrent files overflows")]
+    TotalLengthOverflow,
     #[error("Missing name field in the torrent info")]
     MissingName,
     #[error("Missing piece length field in the torrent info")]
     MissingPieceLength,
+    #[error("Invalid piece length {0}, must be between 1 and u32::MAX")]
+    InvalidPieceLength(i64),
     #[error("Missing pieces field in the torrent info")]
     MissingPieces,
+    #[error("Torrent declares {0} pieces, more than the supported maximum")]
+    TooManyPieces(u64),
+    #[error("Torrent length implies {0} pieces but the info has {1} piece hashes")]
+    PieceCountMismatch(u64, usize),
     #[error("Piece hash error {0}")]
     PieceHash(PieceHashError),
 }
@@ -47,23 +80,54 @@ pub enum TorrentError {
     MissingInfo,
     #[error("Missing info: {0}")]
     MisingInfo(InfoError),
-    #[error("Decoding error")]
-    DecodingError,
+    #[error("Decoding error: {0}")]
+    DecodingError(BencodeError),
 }
 
+/// Upper bound on the number of pieces a torrent may declare, anything above is
+/// rejected at parse time instead of trying to allocate per-piece state for it
+pub const MAX_PIECES: u64 = 1 << 21;
+
 const LENGTH: &[u8] = b"length";
+const FILES: &[u8] = b"files";
+const PATH: &[u8] = b"path";
 const NAME: &[u8] = b"name";
 const PIECE_LENGTH: &[u8] = b"piece length";
 const PIECES: &[u8] = b"pieces";
 
 const ANNOUNCE: &[u8] = b"announce";
 const INFO: &[u8] = b"info";
+const URL_LIST: &[u8] = b"url-list";
+const HTTP_SEEDS: &[u8] = b"httpseeds";
 
 impl Torrent {
+    /// Parses the contents of a .torrent file, hashing the info dictionary over
+    /// its original bytes
+    pub fn from_bytes(data: &[u8]) -> Result<Torrent, TorrentError> {
+        let bencode = Bencode::decode(data).map_err(TorrentError::DecodingError)?;
+        let raw_info = Bencode::raw_dict_value(data, INFO)
+            .map_err(TorrentError::DecodingError)?
+            .ok_or(TorrentError::MissingInfo)?;
+        Self::from_parts(bencode, raw_info.to_vec())
+    }
+
+    /// Builds a torrent from an already decoded tree. There are no original bytes
+    /// here, so the info hash is taken over the canonical encoding of the info dict;
+    /// prefer `from_bytes` for data read from a file or the network.
     pub fn from(data: Bencode) -> Result<Torrent, TorrentError> {
+        let raw_info = data
+            .get(INFO)
+            .map(Bencode::encode_value)
+            .unwrap_or_default();
+        Self::from_parts(data, raw_info)
+    }
+
+    fn from_parts(data: Bencode, raw_info: Vec<u8>) -> Result<Torrent, TorrentError> {
         let announce_field = data.get(ANNOUNCE).ok_or(TorrentError::MissingAnnouce)?;
         let announce = match announce_field {
-            Bencode::Bytes(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+            Bencode::Bytes(bytes) => {
+                String::from_utf8(bytes.clone()).map_err(|_| TorrentError::MissingAnnouce)?
+            }
             _ => return Err(TorrentError::MissingAnnouce),
         };
 
@@ -73,57 +137,150 @@ impl Torrent {
             Err(e) => return Err(TorrentError::MisingInfo(e)),
         };
 
-        let info_hash = Self::calculate_info_hash(&info)?;
+        let info_hash = Self::calculate_info_hash(&raw_info)?;
+
+        let url_list = Self::parse_urls(data.get(URL_LIST));
+        let http_seeds = Self::parse_urls(data.get(HTTP_SEEDS));
+
+        let extra = match data {
+            Bencode::Dict(dict) => dict
+                .into_iter()
+                .filter(|(key, _)| ![ANNOUNCE, INFO].contains(&&key[..]))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
 
         Ok(Torrent {
             announce,
             info,
             info_hash,
+            raw_info,
+            extra,
+            url_list,
+            http_seeds,
         })
     }
 
-    /// Calculates the InfoHash for a given Info dictionary.
-    fn calculate_info_hash(info: &Info) -> Result<InfoHash, TorrentError> {
-        let bencoded_info = Bencode::encode(info);
+    /// Web seed keys may hold a single url or a list of them. Entries that aren't
+    /// UTF-8 strings are skipped as unusable, the original value stays in `extra`
+    fn parse_urls(field: Option<&Bencode>) -> Vec<String> {
+        let to_url = |value: &Bencode| match value {
+            Bencode::Bytes(bytes) => String::from_utf8(bytes.clone()).ok(),
+            _ => None,
+        };
+
+        match field {
+            Some(Bencode::List(list)) => list.iter().filter_map(to_url).collect(),
+            Some(value) => to_url(value).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
 
-        let hash_generic_array = Sha1::digest(&bencoded_info);
+    /// Calculates the InfoHash for a given bencoded Info dictionary.
+    fn calculate_info_hash(bencoded_info: &[u8]) -> Result<InfoHash, TorrentError> {
+        let hash_generic_array = Sha1::digest(bencoded_info);
 
         let hash_array: [u8; 20] = hash_generic_array.into();
         Ok(InfoHash::from(hash_array)) // Use the From<[u8; 20]> impl
     }
 
+    /// Bencodes the metainfo back into the contents of a .torrent file. The info
+    /// dictionary is written from `raw_info` untouched so the info hash is preserved.
+    pub fn to_torrent_bytes(&self) -> Vec<u8> {
+        let mut before_info = self.metainfo_dict();
+        // Keys that sort after `info` are written once the raw info bytes are in place
+        let after_info = before_info.split_off(INFO);
+
+        let mut result = vec![b'd'];
+        let mut write_entry = |key: &[u8], value: &[u8]| {
+            result.extend_from_slice(&Bencode::encode_value(&Bencode::Bytes(key.to_vec())));
+            result.extend_from_slice(value);
+        };
+        for (key, value) in &before_info {
+            write_entry(key, &Bencode::encode_value(value));
+        }
+        write_entry(INFO, &self.raw_info);
+        for (key, value) in &after_info {
+            write_entry(key, &Bencode::encode_value(value));
+        }
+        result.push(b'e');
+        result
+    }
+
+    /// Top-level metainfo dictionary without the info dictionary
+    fn metainfo_dict(&self) -> BTreeMap<Vec<u8>, Bencode> {
+        let mut dict = self.extra.clone();
+        dict.insert(
+            ANNOUNCE.to_vec(),
+            Bencode::Bytes(self.announce.as_bytes().to_vec()),
+        );
+        dict
+    }
+
     pub fn get_announce(&self) -> &str {
         &self.announce
     }
 
     pub fn get_total_pieces(&self) -> u32 {
-        (self.info.length as f64 / self.info.piece_length as f64).ceil() as u32
+        self.info
+            .total_length()
+            .div_ceil(self.info.piece_length as u64) as u32
+    }
+
+    /// Length in bytes of the piece at `index`: `piece_length` for every piece
+    /// but the last, which holds the remainder (or a full piece when the total
+    /// length is an exact multiple). Out of range indices have length 0.
+    pub fn get_piece_len(&self, index: u32) -> u32 {
+        let piece_length = self.info.piece_length as u64;
+        let total_length = self.info.total_length();
+        let offset = (index as u64).saturating_mul(piece_length);
+        if offset >= total_length {
+            return 0;
+        }
+        (total_length - offset).min(piece_length) as u32
     }
 }
 
 impl Encode for Torrent {
     fn to_bencode(&self) -> Bencode {
-        let mut dict = BTreeMap::new();
-        dict.insert(
-            ANNOUNCE.to_vec(),
-            Bencode::Bytes(self.announce.as_bytes().to_vec()),
-        );
-        dict.insert(INFO.to_vec(), self.info.to_bencode());
+        let mut dict = self.metainfo_dict();
+        let info = Bencode::decode(&self.raw_info).unwrap_or_else(|_| self.info.to_bencode());
+        dict.insert(INFO.to_vec(), info);
         Bencode::Dict(dict)
     }
 }
 
 impl Info {
     pub fn from(info_field: &Bencode) -> Result<Info, InfoError> {
-        let length_field = info_field.get(LENGTH).ok_or(InfoError::MissingLength)?;
-        let length = match length_field {
-            Bencode::Int(i) => *i,
-            _ => return Err(InfoError::MissingLength),
+        let length = match info_field.get(LENGTH) {
+            Some(Bencode::Int(i)) if *i >= 0 => Some(*i),
+            Some(Bencode::Int(i)) => return Err(InfoError::InvalidLength(*i)),
+            Some(_) => return Err(InfoError::MissingLength),
+            None => None,
+        };
+
+        let files = match info_field.get(FILES) {
+            Some(Bencode::List(list)) => {
+                Some(list.iter().map(File::from).collect::<Result<Vec<_>, _>>()?)
+            }
+            Some(_) => return Err(InfoError::InvalidFiles),
+            None => None,
+        };
+
+        // A torrent is either single-file (`length`) or multi-file (`files`)
+        let total_length = match (length, &files) {
+            (Some(_), Some(_)) => return Err(InfoError::AmbiguousLayout),
+            (None, None) => return Err(InfoError::MissingLength),
+            (Some(length), None) => length as u64,
+            (None, Some(files)) => files
+                .iter()
+                .try_fold(0u64, |total, file| total.checked_add(file.length as u64))
+                .ok_or(InfoError::TotalLengthOverflow)?,
         };
 
         let name_field = info_field.get(NAME).ok_or(InfoError::MissingName)?;
         let name = match name_field {
-            Bencode::Bytes(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+            Bencode::Bytes(bytes) => String::from_utf8_lossy(bytes).into_owned(),
             _ => return Err(InfoError::MissingName),
         };
 
@@ -131,7 +288,9 @@ impl Info {
             .get(PIECE_LENGTH)
             .ok_or(InfoError::MissingPieceLength)?;
         let piece_length = match plen_field {
-            Bencode::Int(i) => *i,
+            // Piece and block lengths are u32 on the wire
+            Bencode::Int(i) if *i > 0 && *i <= u32::MAX as i64 => *i,
+            Bencode::Int(i) => return Err(InfoError::InvalidPieceLength(*i)),
             _ => return Err(InfoError::MissingPieceLength),
         };
 
@@ -141,28 +300,93 @@ impl Info {
                 if bytes.len() % 20 != 0 {
                     return Err(InfoError::MissingPieces);
                 }
-                let hashes = bytes
+                bytes
                     .chunks_exact(20)
                     .map(|chunk| chunk.try_into().expect("Invalid lenght"))
-                    .collect();
-                hashes
+                    .collect()
             }
             _ => return Err(InfoError::MissingPieces),
         };
 
-        Ok(Info {
+        let info = Info {
             length,
+            files,
             name,
             piece_length,
             pieces,
-        })
+        };
+
+        let total_pieces = total_length.div_ceil(piece_length as u64);
+        if total_pieces > MAX_PIECES {
+            return Err(InfoError::TooManyPieces(total_pieces));
+        }
+        if total_pieces != info.pieces.len() as u64 {
+            return Err(InfoError::PieceCountMismatch(
+                total_pieces,
+                info.pieces.len(),
+            ));
+        }
+
+        Ok(info)
+    }
+
+    /// Total size in bytes of the content, summing every file for multi-file torrents.
+    /// `Info::from` rejects torrents whose total overflows, so this can't overflow.
+    pub fn total_length(&self) -> u64 {
+        match (&self.length, &self.files) {
+            (Some(length), _) => *length as u64,
+            (None, Some(files)) => files.iter().map(|file| file.length as u64).sum(),
+            (None, None) => 0,
+        }
     }
 }
 
-impl Encode for Info {
+impl File {
+    pub fn from(file_field: &Bencode) -> Result<File, InfoError> {
+        let length = match file_field.get(LENGTH) {
+            Some(Bencode::Int(i)) if *i >= 0 => *i,
+            _ => return Err(InfoError::InvalidFiles),
+        };
+
+        let path = match file_field.get(PATH) {
+            Some(Bencode::List(components)) if !components.is_empty() => components
+                .iter()
+                .map(|component| match component {
+                    Bencode::Bytes(bytes) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+                    _ => Err(InfoError::InvalidFiles),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(InfoError::InvalidFiles),
+        };
+
+        Ok(File { length, path })
+    }
+}
+
+impl Encode for File {
     fn to_bencode(&self) -> Bencode {
         let mut dict = BTreeMap::new();
         dict.insert(LENGTH.to_vec(), Bencode::Int(self.length));
+        let path = self
+            .path
+            .iter()
+            .map(|component| Bencode::Bytes(component.as_bytes().to_vec()))
+            .collect();
+        dict.insert(PATH.to_vec(), Bencode::List(path));
+        Bencode::Dict(dict)
+    }
+}
+
+impl Encode for Info {
+    fn to_bencode(&self) -> Bencode {
+        let mut dict = BTreeMap::new();
+        if let Some(length) = self.length {
+            dict.insert(LENGTH.to_vec(), Bencode::Int(length));
+        }
+        if let Some(files) = &self.files {
+            let files = files.iter().map(|file| file.to_bencode()).collect();
+            dict.insert(FILES.to_vec(), Bencode::List(files));
+        }
         dict.insert(NAME.to_vec(), Bencode::Bytes(self.name.as_bytes().to_vec()));
         dict.insert(PIECE_LENGTH.to_vec(), Bencode::Int(self.piece_length));
         let concatendated_hashes: Vec<u8> = self
@@ -176,3 +400,407 @@ impl Encode for Info {
         Bencode::Dict(dict)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(value: &str) -> Bencode {
+        Bencode::Bytes(value.as_bytes().to_vec())
+    }
+
+    fn file(length: i64, path: &[&str]) -> Bencode {
+        let mut dict = BTreeMap::new();
+        dict.insert(LENGTH.to_vec(), Bencode::Int(length));
+        dict.insert(
+            PATH.to_vec(),
+            Bencode::List(path.iter().map(|component| bytes(component)).collect()),
+        );
+        Bencode::Dict(dict)
+    }
+
+    fn info(content: (&[u8], Bencode), piece_length: i64, num_pieces: usize) -> Bencode {
+        let mut dict = BTreeMap::new();
+        dict.insert(content.0.to_vec(), content.1);
+        dict.insert(NAME.to_vec(), bytes("sample"));
+        dict.insert(PIECE_LENGTH.to_vec(), Bencode::Int(piece_length));
+        dict.insert(PIECES.to_vec(), Bencode::Bytes(vec![0u8; 20 * num_pieces]));
+        Bencode::Dict(dict)
+    }
+
+    #[test]
+    fn test_total_length_single_file() {
+        let info = Info::from(&info((LENGTH, Bencode::Int(92063)), 32768, 3)).unwrap();
+        assert_eq!(info.total_length(), 92063);
+        assert!(info.files.is_none());
+    }
+
+    #[test]
+    fn test_total_length_multi_file() {
+        let files = Bencode::List(vec![
+            file(40000, &["a.txt"]),
+            file(30000, &["dir", "b.txt"]),
+        ]);
+        let info = Info::from(&info((FILES, files), 32768, 3)).unwrap();
+        assert_eq!(info.total_length(), 70000);
+        assert!(info.length.is_none());
+
+        let files = info.files.as_ref().unwrap();
+        assert_eq!(files[1].path, vec!["dir".to_string(), "b.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_zero_length_file_between_files() {
+        let files = Bencode::List(vec![
+            file(40000, &["a"]),
+            file(0, &["empty"]),
+            file(30000, &["b"]),
+        ]);
+        let info = Info::from(&info((FILES, files), 32768, 3)).unwrap();
+        assert_eq!(info.total_length(), 70000);
+
+        let files = info.files.as_ref().unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[1].length, 0);
+        assert_eq!(files[1].path, vec!["empty".to_string()]);
+    }
+
+    #[test]
+    fn test_get_total_pieces_multi_file() {
+        let files = Bencode::List(vec![file(40000, &["a"]), file(30000, &["b"])]);
+        let mut dict = BTreeMap::new();
+        dict.insert(ANNOUNCE.to_vec(), bytes("http://tracker/announce"));
+        dict.insert(INFO.to_vec(), info((FILES, files), 32768, 3));
+
+        let torrent = Torrent::from(Bencode::Dict(dict)).unwrap();
+        assert_eq!(torrent.get_total_pieces(), 3);
+    }
+
+    #[test]
+    fn test_parse_web_seeds() {
+        let mut dict = BTreeMap::new();
+        dict.insert(ANNOUNCE.to_vec(), bytes("http://tracker/announce"));
+        dict.insert(INFO.to_vec(), info((LENGTH, Bencode::Int(100)), 32768, 1));
+        dict.insert(URL_LIST.to_vec(), bytes("http://mirror/file"));
+        dict.insert(
+            HTTP_SEEDS.to_vec(),
+            Bencode::List(vec![bytes("http://seed1/"), bytes("http://seed2/")]),
+        );
+
+        let torrent = Torrent::from(Bencode::Dict(dict)).unwrap();
+        assert_eq!(torrent.url_list, vec!["http://mirror/file".to_string()]);
+        assert_eq!(
+            torrent.http_seeds,
+            vec!["http://seed1/".to_string(), "http://seed2/".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_web_seeds_skips_invalid_entries() {
+        let url_list = Bencode::List(vec![
+            bytes("http://mirror1/"),
+            Bencode::Int(5),
+            Bencode::Bytes(vec![0xff, 0xfe]),
+            Bencode::List(vec![bytes("http://nested/")]),
+            bytes("http://mirror2/"),
+        ]);
+        let mut dict = BTreeMap::new();
+        dict.insert(ANNOUNCE.to_vec(), bytes("http://tracker/announce"));
+        dict.insert(INFO.to_vec(), info((LENGTH, Bencode::Int(100)), 32768, 1));
+        dict.insert(URL_LIST.to_vec(), url_list.clone());
+        dict.insert(HTTP_SEEDS.to_vec(), Bencode::Int(1));
+
+        let torrent = Torrent::from(Bencode::Dict(dict)).unwrap();
+        assert_eq!(
+            torrent.url_list,
+            vec!["http://mirror1/".to_string(), "http://mirror2/".to_string()]
+        );
+        assert!(torrent.http_seeds.is_empty());
+        assert_eq!(torrent.extra.get(URL_LIST), Some(&url_list));
+        assert_eq!(torrent.extra.get(HTTP_SEEDS), Some(&Bencode::Int(1)));
+    }
+
+    fn single_file_torrent(length: i64, piece_length: i64) -> Torrent {
+        let num_pieces = (length as u64).div_ceil(piece_length as u64) as usize;
+        let mut dict = BTreeMap::new();
+        dict.insert(ANNOUNCE.to_vec(), bytes("http://tracker/announce"));
+        dict.insert(
+            INFO.to_vec(),
+            info((LENGTH, Bencode::Int(length)), piece_length, num_pieces),
+        );
+        Torrent::from(Bencode::Dict(dict)).unwrap()
+    }
+
+    #[test]
+    fn test_get_piece_len_with_remainder() {
+        let torrent = single_file_torrent(92063, 32768);
+        assert_eq!(torrent.get_total_pieces(), 3);
+        assert_eq!(torrent.get_piece_len(0), 32768);
+        assert_eq!(torrent.get_piece_len(1), 32768);
+        assert_eq!(torrent.get_piece_len(2), 92063 - 2 * 32768);
+        assert_eq!(torrent.get_piece_len(3), 0);
+    }
+
+    #[test]
+    fn test_get_piece_len_exact_multiple() {
+        let torrent = single_file_torrent(3 * 32768, 32768);
+        assert_eq!(torrent.get_total_pieces(), 3);
+        assert_eq!(torrent.get_piece_len(0), 32768);
+        assert_eq!(torrent.get_piece_len(1), 32768);
+        assert_eq!(torrent.get_piece_len(2), 32768);
+        assert_eq!(torrent.get_piece_len(3), 0);
+    }
+
+    #[test]
+    fn test_get_piece_len_sums_to_total_length() {
+        let torrent = single_file_torrent(1_000_003, 16384);
+        let total: u64 = (0..torrent.get_total_pieces())
+            .map(|index| torrent.get_piece_len(index) as u64)
+            .sum();
+        assert_eq!(total, torrent.info.total_length());
+    }
+
+    #[test]
+    fn test_get_total_pieces_tiny_single_piece() {
+        let torrent = single_file_torrent(5 * 1024, 262144);
+        assert_eq!(torrent.get_total_pieces(), 1);
+        assert_eq!(torrent.get_piece_len(0), 5 * 1024);
+    }
+
+    #[test]
+    fn test_reject_enormous_piece_count() {
+        // 1 TiB split into 1 byte pieces
+        let result = Info::from(&info((LENGTH, Bencode::Int(1 << 40)), 1, 1));
+        assert_eq!(result.err(), Some(InfoError::TooManyPieces(1 << 40)));
+    }
+
+    #[test]
+    fn test_reject_piece_count_mismatch() {
+        let result = Info::from(&info((LENGTH, Bencode::Int(92063)), 32768, 2));
+        assert_eq!(result.err(), Some(InfoError::PieceCountMismatch(3, 2)));
+
+        let result = Info::from(&info((LENGTH, Bencode::Int(92063)), 32768, 4));
+        assert_eq!(result.err(), Some(InfoError::PieceCountMismatch(3, 4)));
+    }
+
+    #[test]
+    fn test_reject_non_positive_piece_length() {
+        let result = Info::from(&info((LENGTH, Bencode::Int(100)), 0, 1));
+        assert_eq!(result.err(), Some(InfoError::InvalidPieceLength(0)));
+    }
+
+    #[test]
+    fn test_reject_piece_length_above_u32() {
+        let result = Info::from(&info((LENGTH, Bencode::Int(1 << 33)), 1 << 32, 2));
+        assert_eq!(result.err(), Some(InfoError::InvalidPieceLength(1 << 32)));
+    }
+
+    #[test]
+    fn test_get_piece_len_max_piece_length() {
+        let piece_length = u32::MAX as i64;
+        let torrent = single_file_torrent(2 * piece_length, piece_length);
+        assert_eq!(torrent.get_total_pieces(), 2);
+        assert_eq!(torrent.get_piece_len(0), u32::MAX);
+        assert_eq!(torrent.get_piece_len(1), u32::MAX);
+        assert_eq!(torrent.get_piece_len(5), 0);
+        assert_eq!(torrent.get_piece_len(u32::MAX), 0);
+    }
+
+    #[test]
+    fn test_export_sample_torrent_is_byte_identical() {
+        let data = include_bytes!("../../sample_torrents/sample.torrent");
+        let torrent = Torrent::from_bytes(data).unwrap();
+        assert_eq!(torrent.to_torrent_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn test_export_debian_torrent_keeps_top_level_keys() {
+        let data = include_bytes!("../../sample_torrents/debian-12.10.0-amd64-netinst.iso.torrent");
+        let torrent = Torrent::from_bytes(data).unwrap();
+
+        let exported = torrent.to_torrent_bytes();
+        let keys = |data: &[u8]| match Bencode::decode(data).unwrap() {
+            Bencode::Dict(dict) => dict.into_keys().collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        assert_eq!(keys(&exported), keys(data));
+        assert!(torrent.extra.contains_key(&b"creation date"[..]));
+
+        let reparsed = Torrent::from_bytes(&exported).unwrap();
+        assert_eq!(reparsed.info_hash, torrent.info_hash);
+        assert_eq!(
+            Bencode::decode(&exported).unwrap(),
+            Bencode::decode(data).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_export_keeps_web_seed_keys_verbatim() {
+        let raw_info = [
+            &b"d6:lengthi5e4:name1:x12:piece lengthi5e6:pieces20:"[..],
+            &[7u8; 20],
+            b"e",
+        ]
+        .concat();
+
+        // Single string url-list and a non UTF-8 httpseeds entry
+        let data = [
+            &b"d8:announce3:url9:httpseedsl2:\xff\xfee4:info"[..],
+            &raw_info,
+            b"8:url-list4:httpe",
+        ]
+        .concat();
+        let torrent = Torrent::from_bytes(&data).unwrap();
+        assert_eq!(torrent.url_list, vec!["http".to_string()]);
+        assert!(torrent.http_seeds.is_empty());
+        assert_eq!(torrent.to_torrent_bytes(), data);
+
+        // Empty url-list
+        let data = [&b"d8:announce3:url4:info"[..], &raw_info, b"8:url-listlee"].concat();
+        let torrent = Torrent::from_bytes(&data).unwrap();
+        assert!(torrent.url_list.is_empty());
+        assert_eq!(torrent.to_torrent_bytes(), data);
+    }
+
+    #[test]
+    fn test_info_hash_uses_original_info_bytes() {
+        // Unsorted keys and a non-canonical integer (i03e) that decoding normalises
+        let raw_info = [
+            &b"d4:name1:x6:lengthi5e12:piece lengthi03e6:pieces40:"[..],
+            &[7u8; 40],
+            b"e",
+        ]
+        .concat();
+        let data = [&b"d8:announce3:url4:info"[..], &raw_info, b"e"].concat();
+
+        let torrent = Torrent::from_bytes(&data).unwrap();
+        let expected: [u8; 20] = Sha1::digest(&raw_info).into();
+        assert_eq!(torrent.info_hash, InfoHash::from(expected));
+        assert_eq!(torrent.raw_info, raw_info);
+
+        let canonical = Torrent::from(Bencode::decode(&data).unwrap()).unwrap();
+        assert_ne!(canonical.info_hash, torrent.info_hash);
+
+        let reparsed = Torrent::from_bytes(&torrent.to_torrent_bytes()).unwrap();
+        assert_eq!(reparsed.info_hash, torrent.info_hash);
+    }
+
+    #[test]
+    fn test_info_hash_keeps_unknown_info_keys() {
+        let mut info = match info((LENGTH, Bencode::Int(100)), 32768, 1) {
+            Bencode::Dict(dict) => dict,
+            _ => unreachable!(),
+        };
+        info.insert(b"private".to_vec(), Bencode::Int(1));
+        let info = Bencode::Dict(info);
+        let expected: [u8; 20] = Sha1::digest(Bencode::encode_value(&info)).into();
+
+        let mut dict = BTreeMap::new();
+        dict.insert(ANNOUNCE.to_vec(), bytes("http://tracker/announce"));
+        dict.insert(INFO.to_vec(), info);
+        let torrent = Torrent::from(Bencode::Dict(dict)).unwrap();
+        assert_eq!(torrent.info_hash, InfoHash::from(expected));
+
+        let exported = Bencode::decode(&torrent.to_torrent_bytes()).unwrap();
+        let exported_info = exported.get(INFO).unwrap();
+        assert_eq!(exported_info.get(b"private"), Some(&Bencode::Int(1)));
+    }
+
+    #[test]
+    fn test_invalid_utf8_does_not_panic() {
+        let mut dict = BTreeMap::new();
+        dict.insert(ANNOUNCE.to_vec(), Bencode::Bytes(vec![0xff, 0xfe]));
+        dict.insert(INFO.to_vec(), info((LENGTH, Bencode::Int(100)), 32768, 1));
+        let result = Torrent::from(Bencode::Dict(dict));
+        assert_eq!(result.err(), Some(TorrentError::MissingAnnouce));
+
+        let mut info = match info((LENGTH, Bencode::Int(100)), 32768, 1) {
+            Bencode::Dict(dict) => dict,
+            _ => unreachable!(),
+        };
+        info.insert(NAME.to_vec(), Bencode::Bytes(vec![b'a', 0xff]));
+        let info = Info::from(&Bencode::Dict(info)).unwrap();
+        assert_eq!(info.name, "a\u{fffd}");
+    }
+
+    #[test]
+    fn test_extreme_integers_do_not_panic() {
+        let extremes = [
+            i64::MIN,
+            -1,
+            0,
+            1,
+            u32::MAX as i64,
+            u32::MAX as i64 + 1,
+            i64::MAX - 1,
+            i64::MAX,
+        ];
+        let check = |info_field: Bencode| {
+            let mut dict = BTreeMap::new();
+            dict.insert(ANNOUNCE.to_vec(), bytes("http://tracker/announce"));
+            dict.insert(INFO.to_vec(), info_field);
+            let data = Bencode::encode_value(&Bencode::Dict(dict));
+            if let Ok(torrent) = Torrent::from_bytes(&data) {
+                let total_pieces = torrent.get_total_pieces();
+                torrent.get_piece_len(0);
+                torrent.get_piece_len(total_pieces.saturating_sub(1));
+                torrent.get_piece_len(u32::MAX);
+            }
+        };
+
+        for &length in &extremes {
+            for &piece_length in &extremes {
+                for num_pieces in 0..3 {
+                    check(info(
+                        (LENGTH, Bencode::Int(length)),
+                        piece_length,
+                        num_pieces,
+                    ));
+
+                    let files = Bencode::List(vec![file(length, &["a"]); 3]);
+                    check(info((FILES, files), piece_length, num_pieces));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_length_and_files() {
+        let result = Info::from(&info((b"other", Bencode::Int(1)), 32768, 1));
+        assert_eq!(result.err(), Some(InfoError::MissingLength));
+    }
+
+    #[test]
+    fn test_negative_or_non_integer_length() {
+        let result = Info::from(&info((LENGTH, Bencode::Int(-5)), 32768, 1));
+        assert_eq!(result.err(), Some(InfoError::InvalidLength(-5)));
+
+        let result = Info::from(&info((LENGTH, Bencode::Int(i64::MIN)), 32768, 1));
+        assert_eq!(result.err(), Some(InfoError::InvalidLength(i64::MIN)));
+
+        let result = Info::from(&info((LENGTH, bytes("5")), 32768, 1));
+        assert_eq!(result.err(), Some(InfoError::MissingLength));
+    }
+
+    #[test]
+    fn test_both_length_and_files() {
+        let mut info = match info((LENGTH, Bencode::Int(100)), 32768, 1) {
+            Bencode::Dict(dict) => dict,
+            _ => unreachable!(),
+        };
+        info.insert(FILES.to_vec(), Bencode::List(vec![file(100, &["a"])]));
+        let result = Info::from(&Bencode::Dict(info));
+        assert_eq!(result.err(), Some(InfoError::AmbiguousLayout));
+    }
+
+    #[test]
+    fn test_reject_overflowing_total_length() {
+        let files = Bencode::List(vec![
+            file(i64::MAX, &["a"]),
+            file(i64::MAX, &["b"]),
+            file(i64::MAX, &["c"]),
+        ]);
+        let result = Info::from(&info((FILES, files), 32768, 1));
+        assert_eq!(result.err(), Some(InfoError::TotalLengthOverflow));
+    }
+}
@@ -6,7 +6,7 @@ use std::{
 use thiserror::Error;
 
 use crate::{
-    bencode::{Bencode, BencodeError},
+    bencode::BencodeError,
     metainfo::{Torrent, TorrentError},
 };
 
@@ -30,13 +30,9 @@ impl TorrentParser {
             Err(e) => return Err(ParseError::IOError(e)),
         };
 
-        let bencoded_data = match Bencode::decode(&data) {
-            Ok(data) => data,
-            Err(e) => return Err(ParseError::BencodeError(e)),
-        };
-
-        let torrent = match Torrent::from(bencoded_data) {
+        let torrent = match Torrent::from_bytes(&data) {
             Ok(torrent) => torrent,
+            Err(TorrentError::DecodingError(e)) => return Err(ParseError::BencodeError(e)),
             Err(e) => return Err(ParseError::TorrentError(e)),
         };
 
@@ -87,6 +83,7 @@ mod tests {
         let expected_info_hash = "d69f91e6b2ae4c542468d1073a71d4ea13879a7f";
         assert_eq!(torrent.announce, expected_tracker_url.to_string());
         assert_eq!(torrent.info_hash.to_hex(), expected_info_hash);
-        assert_eq!(torrent.info.length, length);
+        assert_eq!(torrent.info.length, Some(length));
+        assert_eq!(torrent.info.total_length(), length as u64);
     }
 }